@@ -2,11 +2,15 @@
 
 use crate::{
     config::{MAX_SYSCALL_NUM, PAGE_SIZE},
-    mm::translated_phisical_address,
+    loader::get_app_data_by_name,
+    mm::{translated_phisical_address, translated_str},
     task::{
-        change_program_brk, current_user_token, exit_current_and_run_next, get_current_start_time,
-        get_current_status, get_syscall_times, mmap_current_task, munmap_current_task,
-        suspend_current_and_run_next, TaskStatus,
+        change_program_brk, current_pid, current_user_token, exec_current_task,
+        exit_current_and_run_next, fork_current_task, get_current_start_time, get_current_status,
+        get_syscall_profile, get_syscall_times, mmap_current_task, munmap_current_task,
+        set_priority_current_task, spawn_current_task, suspend_current_and_run_next,
+        thread_create_current_task, trace_ctl, waitpid_current_task, waittid_current_task,
+        SyscallRecord, TaskStatus,
     },
     timer::get_time_us,
 };
@@ -30,9 +34,9 @@ pub struct TaskInfo {
 }
 
 /// task exits and submit an exit code
-pub fn sys_exit(_exit_code: i32) -> ! {
+pub fn sys_exit(exit_code: i32) -> ! {
     trace!("kernel: sys_exit");
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
@@ -104,3 +108,123 @@ pub fn sys_sbrk(size: i32) -> isize {
         -1
     }
 }
+
+/// set the current thread's stride scheduling priority and return it;
+/// rejects `prio < 2` with -1
+pub fn sys_set_priority(prio: isize) -> isize {
+    trace!("kernel: sys_set_priority");
+    set_priority_current_task(prio)
+}
+
+/// get the pid of the current process
+pub fn sys_getpid() -> isize {
+    trace!("kernel: sys_getpid");
+    current_pid() as isize
+}
+
+/// spawn a child that is a deep copy of the calling process; the child gets
+/// a return value of 0, the parent gets the child's pid
+pub fn sys_fork() -> isize {
+    trace!("kernel: sys_fork");
+    let new_task = fork_current_task();
+    new_task.process.upgrade().unwrap().getpid() as isize
+}
+
+/// replace the address space of the calling process with the named
+/// application's image
+pub fn sys_exec(path: *const u8) -> isize {
+    trace!("kernel: sys_exec");
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    if let Some(data) = get_app_data_by_name(path.as_str()) {
+        exec_current_task(data);
+        0
+    } else {
+        -1
+    }
+}
+
+/// spawn a child running the named application directly, without first
+/// deep-copying the caller's address space the way `fork` + `exec` would;
+/// returns the child's pid, or -1 if the application doesn't exist
+pub fn sys_spawn(path: *const u8) -> isize {
+    trace!("kernel: sys_spawn");
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    if let Some(data) = get_app_data_by_name(path.as_str()) {
+        spawn_current_task(data).process.upgrade().unwrap().getpid() as isize
+    } else {
+        -1
+    }
+}
+
+/// Copy `value` into user space at `dst`, translating one byte at a time so
+/// the write stays correct even when `value` straddles two physical pages.
+fn copy_to_user<T: Copy>(token: usize, dst: *mut T, value: &T) {
+    let src = value as *const T as *const u8;
+    for i in 0..core::mem::size_of::<T>() {
+        let byte_va = unsafe { (dst as *const u8).add(i) };
+        let byte_pa = translated_phisical_address(token, byte_va) as *mut u8;
+        unsafe {
+            *byte_pa = *src.add(i);
+        }
+    }
+}
+
+/// copy this task's per-syscall call count and cumulative service time table
+/// (one [`SyscallRecord`] per syscall id, `MAX_SYSCALL_NUM` entries) into
+/// `buf`
+pub fn sys_syscall_profile(buf: *mut SyscallRecord) -> isize {
+    trace!("kernel: sys_syscall_profile");
+    let token = current_user_token();
+    let profile = get_syscall_profile();
+    for (i, record) in profile.iter().enumerate() {
+        copy_to_user(token, unsafe { buf.add(i) }, record);
+    }
+    0
+}
+
+/// turn kernel task-switch tracing on or off; while on, `run_tasks` logs
+/// every fetch and idle->task switch, and the yield, timer-preemption and
+/// exit paths log each task->idle switch, preemption and exit
+pub fn sys_trace_ctl(on: bool) -> isize {
+    trace!("kernel: sys_trace_ctl");
+    trace_ctl(on);
+    0
+}
+
+/// create a new thread of the calling process starting at `entry` with
+/// `arg` passed through in `a0`; returns the new thread's tid
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    trace!("kernel: sys_thread_create");
+    thread_create_current_task(entry, arg)
+}
+
+/// wait for thread `tid` of the calling process to exit, returning its
+/// exit code; -1 if no such thread exists (or `tid` is the caller's own),
+/// -2 if it exists but hasn't exited yet
+pub fn sys_waittid(tid: usize) -> isize {
+    trace!("kernel: sys_waittid");
+    waittid_current_task(tid)
+}
+
+/// wait for a child process (`pid`, or any child if `pid == -1`) to exit,
+/// writing its exit code through `exit_code_ptr`; returns the child's pid on
+/// success, -1 if there is no such child, -2 if it exists but hasn't
+/// exited yet
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    trace!("kernel: sys_waitpid");
+    match waitpid_current_task(pid) {
+        Ok((found_pid, exit_code)) => {
+            let exit_code_ptr = translated_phisical_address(
+                current_user_token(),
+                exit_code_ptr as *const u8,
+            ) as *mut i32;
+            unsafe {
+                *exit_code_ptr = exit_code;
+            }
+            found_pid as isize
+        }
+        Err(code) => code,
+    }
+}
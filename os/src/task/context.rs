@@ -0,0 +1,37 @@
+//! Implementation of [`TaskContext`]
+
+use crate::trap::trap_return;
+
+/// Callee-saved registers preserved across a `__switch` between two tasks'
+/// kernel control flow
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TaskContext {
+    /// return address, points `__switch` back into `ra`'s caller
+    ra: usize,
+    /// kernel stack pointer of this task
+    sp: usize,
+    /// s0..s11
+    s: [usize; 12],
+}
+
+impl TaskContext {
+    /// An all-zero task context, used before a task has ever run
+    pub fn zero_init() -> Self {
+        Self {
+            ra: 0,
+            sp: 0,
+            s: [0; 12],
+        }
+    }
+
+    /// Build a task context that, once switched to, returns into
+    /// `trap_return` with the given kernel stack pointer
+    pub fn goto_trap_return(kstack_ptr: usize) -> Self {
+        Self {
+            ra: trap_return as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
+}
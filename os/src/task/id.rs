@@ -0,0 +1,110 @@
+//! Per-thread user-space resources: a thread id and the user stack +
+//! trap-context page that live at a deterministic offset computed from it.
+//!
+//! Every thread of a process (including its first one) owns a
+//! [`TaskUserRes`]. The resources are mapped into the *process's* address
+//! space (threads don't have one of their own) and unmapped automatically
+//! when the `TaskUserRes` is dropped.
+
+use super::process::ProcessControlBlock;
+use crate::config::{PAGE_SIZE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::mm::{MapPermission, PhysPageNum, VirtAddr};
+use alloc::sync::{Arc, Weak};
+
+/// A thread's tid plus the user stack and trap-context page derived from it
+pub struct TaskUserRes {
+    /// Thread id, unique within the owning process, recycled on drop
+    pub tid: usize,
+    /// Base address threads of this process lay their stacks out below
+    pub ustack_base: usize,
+    /// The process this thread belongs to
+    pub process: Weak<ProcessControlBlock>,
+}
+
+impl TaskUserRes {
+    /// Allocate a tid for a new thread of `process` and, if `alloc_user_res`
+    /// is set, map its user stack and trap-context page. Pass `false` when
+    /// the caller already knows the mapping exists (e.g. the child of a
+    /// `fork`, whose address space was deep-copied from the parent).
+    pub fn new(process: Arc<ProcessControlBlock>, ustack_base: usize, alloc_user_res: bool) -> Self {
+        let tid = process.inner_exclusive_access().alloc_tid();
+        let task_user_res = Self {
+            tid,
+            ustack_base,
+            process: Arc::downgrade(&process),
+        };
+        if alloc_user_res {
+            task_user_res.alloc_user_res();
+        }
+        task_user_res
+    }
+
+    /// Lowest address of this thread's user stack
+    pub fn ustack_bottom(&self) -> usize {
+        self.ustack_base - self.tid * (PAGE_SIZE + USER_STACK_SIZE)
+    }
+
+    /// Highest address of this thread's user stack
+    pub fn ustack_top(&self) -> usize {
+        self.ustack_bottom() + USER_STACK_SIZE
+    }
+
+    /// Virtual address of this thread's trap-context page
+    pub fn trap_cx_user_va(&self) -> usize {
+        TRAP_CONTEXT - self.tid * PAGE_SIZE
+    }
+
+    /// Physical page number backing this thread's trap-context page, looked
+    /// up in the owning process's address space
+    pub fn trap_cx_ppn(&self) -> PhysPageNum {
+        let process = self.process.upgrade().unwrap();
+        let inner = process.inner_exclusive_access();
+        inner
+            .memory_set
+            .translate(VirtAddr::from(self.trap_cx_user_va()).into())
+            .unwrap()
+            .ppn()
+    }
+
+    /// Map this thread's user stack and trap-context page into the process
+    pub fn alloc_user_res(&self) {
+        let process = self.process.upgrade().unwrap();
+        let mut inner = process.inner_exclusive_access();
+        inner.memory_set.insert_framed_area(
+            self.ustack_bottom().into(),
+            self.ustack_top().into(),
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        inner.memory_set.insert_framed_area(
+            self.trap_cx_user_va().into(),
+            (self.trap_cx_user_va() + PAGE_SIZE).into(),
+            MapPermission::R | MapPermission::W,
+        );
+    }
+
+    /// Unmap this thread's user stack and trap-context page from the process
+    pub fn dealloc_user_res(&self) {
+        let process = self.process.upgrade().unwrap();
+        let mut inner = process.inner_exclusive_access();
+        let ustack_bottom_va: VirtAddr = self.ustack_bottom().into();
+        inner
+            .memory_set
+            .remove_area_with_start_vpn(ustack_bottom_va.into());
+        let trap_cx_bottom_va: VirtAddr = self.trap_cx_user_va().into();
+        inner
+            .memory_set
+            .remove_area_with_start_vpn(trap_cx_bottom_va.into());
+    }
+
+    fn dealloc_tid(&self) {
+        let process = self.process.upgrade().unwrap();
+        process.inner_exclusive_access().dealloc_tid(self.tid);
+    }
+}
+
+impl Drop for TaskUserRes {
+    fn drop(&mut self) {
+        self.dealloc_tid();
+        self.dealloc_user_res();
+    }
+}
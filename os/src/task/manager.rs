@@ -0,0 +1,81 @@
+//! Implementation of [`TaskManager`]
+//!
+//! Holds every task that is ready to run but not currently on the CPU. The
+//! `Processor` pulls from here via `fetch_task` and tasks are returned to it
+//! via `add_task` whenever they become runnable again. Tasks are picked by
+//! stride scheduling rather than FIFO order: the ready task with the
+//! smallest stride goes next, and its stride is bumped by its pass before
+//! it's handed back.
+
+use super::task::{stride_less, BIG_STRIDE};
+use super::TaskControlBlock;
+use crate::sync::UPSafeCell;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+/// Ready queue of tasks awaiting stride scheduling
+pub struct TaskManager {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl TaskManager {
+    /// Create an empty `TaskManager`
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+
+    /// Add a task to the ready queue
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+
+    /// Remove and return the ready task with the smallest stride, advancing
+    /// its stride by `BIG_STRIDE / priority` so it falls behind again
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (min_idx, _) = self
+            .ready_queue
+            .iter()
+            .enumerate()
+            .map(|(i, task)| (i, task.inner_exclusive_access().stride))
+            .reduce(|(bi, bs), (i, s)| if stride_less(s, bs) { (i, s) } else { (bi, bs) })?;
+        let task = self.ready_queue.remove(min_idx).unwrap();
+        let mut inner = task.inner_exclusive_access();
+        let pass = BIG_STRIDE / inner.priority;
+        inner.stride = inner.stride.wrapping_add(pass);
+        drop(inner);
+        Some(task)
+    }
+
+    /// Remove `task` from the ready queue if it's in it, e.g. a sibling
+    /// thread being torn down out from under a process exit/exec before it
+    /// is ever fetched and scheduled again
+    pub fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        if let Some(idx) = self.ready_queue.iter().position(|t| Arc::ptr_eq(t, task)) {
+            self.ready_queue.remove(idx);
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
+        unsafe { UPSafeCell::new(TaskManager::new()) };
+}
+
+/// Add a task to the ready queue
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+
+/// Take a task off the ready queue
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch()
+}
+
+/// Remove a task from the ready queue without fetching it, e.g. a sibling
+/// thread being torn down before it's ever scheduled again
+pub fn remove_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().remove(task);
+}
@@ -0,0 +1,509 @@
+//! Task management implementation
+//!
+//! Process state and thread state are split across two types: a
+//! [`ProcessControlBlock`] ([`process`]) holds what a process's threads
+//! share (address space, process tree, heap), while a [`TaskControlBlock`]
+//! ([`task`]) is one thread (trap context, kernel stack, scheduling). Tid
+//! allocation and the per-thread user stack/trap-context page live in
+//! [`id`]; pid/kernel-stack-slot allocation in [`pid`]; the ready queue in
+//! [`manager`]; the running-thread bookkeeping in [`processor`]; and the
+//! `__switch` context switch in [`switch`]/[`context`]. Other modules reach
+//! process/thread management through the functions re-exported here rather
+//! than by poking at the submodules directly.
+
+mod context;
+mod id;
+mod manager;
+mod pid;
+mod process;
+mod processor;
+mod switch;
+#[allow(clippy::module_inception)]
+mod task;
+mod trace;
+
+use crate::config::MAX_SYSCALL_NUM;
+use crate::loader::get_app_data_by_name;
+use crate::mm::KERNEL_SPACE;
+use crate::timer::get_time_us;
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::Arc;
+use lazy_static::*;
+use manager::{fetch_task, remove_task};
+use process::ProcessControlBlock;
+use switch::__switch;
+use task::TaskControlBlockInner;
+
+pub use context::TaskContext;
+pub use id::TaskUserRes;
+pub use manager::add_task;
+pub use pid::{kernel_stack_position, pid_alloc, KernelStack, PidHandle};
+pub use processor::{
+    current_task, current_trap_cx, run_tasks, schedule, take_current_task,
+};
+pub use task::{SyscallRecord, TaskControlBlock, TaskStatus};
+pub use trace::{trace_ctl, TraceEvent};
+
+lazy_static! {
+    /// The first user-space process, the ancestor of every other process in
+    /// the system; when a process exits, its children are re-parented here.
+    pub static ref INITPROC: Arc<ProcessControlBlock> = {
+        let (process, ustack_base, entry_point) =
+            ProcessControlBlock::new(get_app_data_by_name("initproc").unwrap());
+        let main_thread = Arc::new(TaskControlBlock::new(Arc::clone(&process), ustack_base, true));
+        init_main_thread_trap_cx(&main_thread, entry_point);
+        process.inner_exclusive_access().tasks.push(Some(main_thread));
+        process
+    };
+}
+
+/// Build the initial trap context for a thread's first run
+fn init_main_thread_trap_cx(thread: &Arc<TaskControlBlock>, entry_point: usize) {
+    let thread_inner = thread.inner_exclusive_access();
+    let ustack_top = thread_inner.res.as_ref().unwrap().ustack_top();
+    let kstack_top = thread.kernel_stack.get_top();
+    let trap_cx = thread_inner.get_trap_cx();
+    *trap_cx = TrapContext::app_init_context(
+        entry_point,
+        ustack_top,
+        KERNEL_SPACE.exclusive_access().token(),
+        kstack_top,
+        trap_handler as usize,
+    );
+}
+
+/// Enqueue [`INITPROC`]'s main thread; called once during kernel init
+pub fn add_initproc() {
+    let main_thread = INITPROC.inner_exclusive_access().tasks[0]
+        .as_ref()
+        .unwrap()
+        .clone();
+    add_task(main_thread);
+}
+
+/// Suspend the current thread and switch to the next ready thread
+pub fn suspend_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let pid = current_thread_pid(&task);
+    let tid = task.tid();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    let status_before = task_inner.task_status;
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    trace::emit(
+        TraceEvent::TaskToIdle,
+        Some(pid),
+        Some(tid),
+        Some(status_before),
+        Some(TaskStatus::Ready),
+    );
+    add_task(task);
+    schedule(task_cx_ptr);
+}
+
+/// Suspend the current thread because the timer interrupt fired, tracing it
+/// as a preemption rather than a voluntary `sys_yield`, and switch to the
+/// next ready thread. Called from the timer interrupt path in the trap
+/// handler.
+pub fn preempt_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let pid = current_thread_pid(&task);
+    let tid = task.tid();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    let status_before = task_inner.task_status;
+    task_inner.task_status = TaskStatus::Ready;
+    trace::emit(
+        TraceEvent::TimerPreempt,
+        Some(pid),
+        Some(tid),
+        Some(status_before),
+        Some(TaskStatus::Ready),
+    );
+    drop(task_inner);
+    add_task(task);
+    schedule(task_cx_ptr);
+}
+
+/// Remove every thread of `process` other than tid `keep_tid` from the
+/// ready queue and free its user-space resources (tid, user stack,
+/// trap-context page). Must run before the process's address space is
+/// torn down or replaced: a sibling thread can still be sitting in
+/// `TASK_MANAGER`'s ready queue, and leaving it there past that point means
+/// it gets fetched and scheduled back in through a `trap_cx_ppn` pointing
+/// at memory that's already been freed (or reallocated to someone else).
+fn teardown_other_threads(process: &ProcessControlBlock, keep_tid: usize) {
+    let mut inner = process.inner_exclusive_access();
+    let mut others = alloc::vec::Vec::new();
+    for (tid, slot) in inner.tasks.iter_mut().enumerate() {
+        if tid == keep_tid {
+            continue;
+        }
+        if let Some(other) = slot.take() {
+            remove_task(&other);
+            others.push(other);
+        }
+    }
+    // dropping a `TaskUserRes` borrows the process again, so release it first
+    drop(inner);
+    for other in others {
+        other.inner_exclusive_access().res = None;
+    }
+}
+
+/// Exit the current thread with `exit_code`, releasing its user-space
+/// resources, and switch to the next ready thread. If this is a process's
+/// main thread (tid 0), the whole process exits with it: its children are
+/// reparented onto [`INITPROC`] and it becomes a zombie for its own parent
+/// to reap via `sys_waitpid`. Other threads can exit independently and are
+/// reaped individually via `sys_waittid`.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    let task = take_current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let pid = process.getpid();
+    let tid = task.tid();
+
+    let mut task_inner = task.inner_exclusive_access();
+    let status_before = task_inner.task_status;
+    task_inner.task_status = TaskStatus::Zombie;
+    task_inner.exit_code = Some(exit_code);
+    task_inner.res = None; // unmap this thread's stack/trap-cx page, free its tid
+    drop(task_inner);
+    trace::emit(
+        TraceEvent::Exit,
+        Some(pid),
+        Some(tid),
+        Some(status_before),
+        Some(TaskStatus::Zombie),
+    );
+    drop(task);
+
+    if tid == 0 {
+        // every other thread is about to lose the address space it runs in;
+        // tear each of them down before the address space itself goes away
+        teardown_other_threads(&process, tid);
+
+        let mut inner = process.inner_exclusive_access();
+        inner.exit_code = Some(exit_code);
+
+        {
+            let mut initproc_inner = INITPROC.inner_exclusive_access();
+            for child in inner.children.iter() {
+                child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+                initproc_inner.children.push(child.clone());
+            }
+        }
+        inner.children.clear();
+        inner.memory_set.recycle_data_pages();
+        inner.tasks.clear();
+    }
+
+    let mut _unused = TaskContext::zero_init();
+    schedule(&mut _unused as *mut _);
+}
+
+/// Get the current thread's (i.e. its process's) page table token
+pub fn current_user_token() -> usize {
+    processor::current_user_token()
+}
+
+fn current_thread_pid(task: &Arc<TaskControlBlock>) -> usize {
+    task.process.upgrade().unwrap().getpid()
+}
+
+fn with_current_inner<T>(f: impl FnOnce(&TaskControlBlockInner) -> T) -> T {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    f(&inner)
+}
+
+/// Current thread's scheduling status
+pub fn get_current_status() -> TaskStatus {
+    with_current_inner(|inner| inner.task_status)
+}
+
+/// Timestamp (us) the current thread was first scheduled
+pub fn get_current_start_time() -> usize {
+    with_current_inner(|inner| inner.start_time)
+}
+
+/// Per-syscall call counts for the current thread
+pub fn get_syscall_times() -> [u32; MAX_SYSCALL_NUM] {
+    with_current_inner(|inner| {
+        let mut times = [0u32; MAX_SYSCALL_NUM];
+        for (i, record) in inner.syscall_profile.iter().enumerate() {
+            times[i] = record.count;
+        }
+        times
+    })
+}
+
+/// Full per-syscall call count and cumulative service time table for the
+/// current thread
+pub fn get_syscall_profile() -> [SyscallRecord; MAX_SYSCALL_NUM] {
+    with_current_inner(|inner| inner.syscall_profile)
+}
+
+/// Record one more call to `syscall_id` against the current thread, adding
+/// `elapsed_us` to its cumulative service time
+pub fn record_syscall(syscall_id: usize, elapsed_us: usize) {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let record = &mut inner.syscall_profile[syscall_id];
+    record.count += 1;
+    record.time_us += elapsed_us;
+}
+
+/// Set the current thread's stride scheduling priority; rejects `prio < 2`
+pub fn set_priority_current_task(prio: isize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.set_priority(prio)
+}
+
+/// Mark the current thread as scheduled for the first time, if it hasn't
+/// run yet
+pub fn mark_current_started() {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.start_time == 0 {
+        inner.start_time = get_time_us();
+    }
+}
+
+/// Map `[start, start + len)` into the current process's address space
+pub fn mmap_current_task(start: usize, len: usize, prot: usize) -> isize {
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    let mut inner = process.inner_exclusive_access();
+    inner.memory_set.mmap(start, len, prot)
+}
+
+/// Unmap `[start, start + len)` from the current process's address space
+pub fn munmap_current_task(start: usize, len: usize) -> isize {
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    let mut inner = process.inner_exclusive_access();
+    inner.memory_set.munmap(start, len)
+}
+
+/// Grow or shrink the current process's heap by `size` bytes, returning the
+/// old program break
+pub fn change_program_brk(size: i32) -> Option<usize> {
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    let mut inner = process.inner_exclusive_access();
+    let old_brk = inner.program_brk;
+    let new_brk = old_brk as isize + size as isize;
+    if new_brk < inner.heap_bottom as isize {
+        return None;
+    }
+    let result = if size < 0 {
+        inner.memory_set.shrink_to(
+            (inner.heap_bottom as usize).into(),
+            (new_brk as usize).into(),
+        )
+    } else {
+        inner.memory_set.append_to(
+            (inner.heap_bottom as usize).into(),
+            (new_brk as usize).into(),
+        )
+    };
+    if result {
+        inner.program_brk = new_brk as usize;
+        Some(old_brk)
+    } else {
+        None
+    }
+}
+
+/// Fork the current process (deep-copying its address space and its calling
+/// thread's trap context into a fresh main thread), enqueue the child's
+/// main thread and return it
+pub fn fork_current_task() -> Arc<TaskControlBlock> {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let ustack_base = task.inner_exclusive_access().res.as_ref().unwrap().ustack_base;
+
+    let child_process = process.fork();
+    // the address space was deep-copied whole, so the child's main thread's
+    // stack/trap-cx page already exist at the same addresses
+    let child_main_thread = Arc::new(TaskControlBlock::new(
+        Arc::clone(&child_process),
+        ustack_base,
+        false,
+    ));
+    child_process
+        .inner_exclusive_access()
+        .tasks
+        .push(Some(Arc::clone(&child_main_thread)));
+    // TaskControlBlock::new always starts a thread at DEFAULT_PRIORITY;
+    // inherit the parent's stride-scheduling priority instead, the same way
+    // its address space and trap context are inherited
+    child_main_thread.inner_exclusive_access().priority =
+        task.inner_exclusive_access().priority;
+
+    let trap_cx = child_main_thread.inner_exclusive_access().get_trap_cx();
+    *trap_cx = *task.inner_exclusive_access().get_trap_cx();
+    trap_cx.kernel_sp = child_main_thread.kernel_stack.get_top();
+    // the child's fork() return value is 0, unlike the parent's (its own pid)
+    trap_cx.x[10] = 0;
+
+    add_task(child_main_thread.clone());
+    child_main_thread
+}
+
+/// Replace the current process's address space with `elf_data`. Every
+/// thread but the caller is torn down; the caller becomes the sole (main)
+/// thread of the freshly loaded image.
+pub fn exec_current_task(elf_data: &[u8]) {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let tid = task.tid();
+
+    // every other thread is about to lose the address space it runs in;
+    // tear each of them down before the address space itself is replaced
+    teardown_other_threads(&process, tid);
+    // drop this thread's old user-space resources before the address space
+    // they live in is replaced
+    task.inner_exclusive_access().res = None;
+
+    let (ustack_base, entry_point) = process.exec(elf_data);
+
+    let new_res = TaskUserRes::new(Arc::clone(&process), ustack_base, true);
+    let ustack_top = new_res.ustack_top();
+    let trap_cx_ppn = new_res.trap_cx_ppn();
+    let kstack_top = task.kernel_stack.get_top();
+
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.res = Some(new_res);
+    task_inner.trap_cx_ppn = trap_cx_ppn;
+    let trap_cx = task_inner.get_trap_cx();
+    *trap_cx = TrapContext::app_init_context(
+        entry_point,
+        ustack_top,
+        KERNEL_SPACE.exclusive_access().token(),
+        kstack_top,
+        trap_handler as usize,
+    );
+}
+
+/// Create a fresh process running `elf_data` directly, linked under the
+/// current process as its child, without first deep-copying the caller's
+/// address space the way `fork` does. Used by `sys_spawn`.
+pub fn spawn_current_task(elf_data: &[u8]) -> Arc<TaskControlBlock> {
+    let current = current_task().unwrap().process.upgrade().unwrap();
+    let (new_process, ustack_base, entry_point) = ProcessControlBlock::new(elf_data);
+    new_process.inner_exclusive_access().parent = Some(Arc::downgrade(&current));
+    current
+        .inner_exclusive_access()
+        .children
+        .push(Arc::clone(&new_process));
+
+    let main_thread = Arc::new(TaskControlBlock::new(
+        Arc::clone(&new_process),
+        ustack_base,
+        true,
+    ));
+    init_main_thread_trap_cx(&main_thread, entry_point);
+    new_process
+        .inner_exclusive_access()
+        .tasks
+        .push(Some(Arc::clone(&main_thread)));
+
+    add_task(main_thread.clone());
+    main_thread
+}
+
+/// Create a new thread of the current process starting at `entry` with
+/// `arg` in `a0`, enqueue it and return its tid
+pub fn thread_create_current_task(entry: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let ustack_base = task.inner_exclusive_access().res.as_ref().unwrap().ustack_base;
+
+    let new_task = Arc::new(TaskControlBlock::new(Arc::clone(&process), ustack_base, true));
+    let tid = new_task.tid();
+
+    {
+        let mut inner = process.inner_exclusive_access();
+        while inner.tasks.len() <= tid {
+            inner.tasks.push(None);
+        }
+        inner.tasks[tid] = Some(Arc::clone(&new_task));
+    }
+
+    let new_task_inner = new_task.inner_exclusive_access();
+    let ustack_top = new_task_inner.res.as_ref().unwrap().ustack_top();
+    let kstack_top = new_task.kernel_stack.get_top();
+    let trap_cx = new_task_inner.get_trap_cx();
+    *trap_cx = TrapContext::app_init_context(
+        entry,
+        ustack_top,
+        KERNEL_SPACE.exclusive_access().token(),
+        kstack_top,
+        trap_handler as usize,
+    );
+    trap_cx.x[10] = arg;
+    drop(new_task_inner);
+
+    add_task(new_task);
+    tid as isize
+}
+
+/// Reap the current process's thread `tid`, returning its exit code, or -2
+/// if it's still running, or -1 if it doesn't exist (or `tid` is the
+/// calling thread's own)
+pub fn waittid_current_task(tid: usize) -> isize {
+    let task = current_task().unwrap();
+    if task.tid() == tid {
+        return -1;
+    }
+    let process = task.process.upgrade().unwrap();
+    let mut inner = process.inner_exclusive_access();
+    let Some(Some(target)) = inner.tasks.get(tid) else {
+        return -1;
+    };
+    let target = Arc::clone(target);
+    let target_inner = target.inner_exclusive_access();
+    if !target_inner.is_zombie() {
+        return -2;
+    }
+    let exit_code = target_inner.exit_code.unwrap();
+    drop(target_inner);
+    drop(target);
+    inner.tasks[tid] = None;
+    exit_code as isize
+}
+
+/// The pid of the currently running thread's process
+pub fn current_pid() -> usize {
+    current_task().unwrap().process.upgrade().unwrap().getpid()
+}
+
+/// Search the current process's children for a zombie matching `pid` (or
+/// any zombie child if `pid == -1`), reap it and report its exit code.
+///
+/// Returns `(child_pid, exit_code)` on success, `-1` if no such child exists
+/// at all, or `-2` if a matching child exists but hasn't exited yet.
+pub fn waitpid_current_task(pid: isize) -> Result<(usize, i32), isize> {
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    let mut inner = process.inner_exclusive_access();
+    if !inner
+        .children
+        .iter()
+        .any(|p| pid == -1 || pid as usize == p.getpid())
+    {
+        return Err(-1);
+    }
+    let pair = inner.children.iter().enumerate().find(|(_, p)| {
+        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+    });
+    if let Some((idx, _)) = pair {
+        let child = inner.children.remove(idx);
+        assert_eq!(Arc::strong_count(&child), 1);
+        let found_pid = child.getpid();
+        let exit_code = child.inner_exclusive_access().exit_code.unwrap();
+        Ok((found_pid, exit_code))
+    } else {
+        Err(-2)
+    }
+}
@@ -0,0 +1,135 @@
+//! Implementation of [`RecycleAllocator`] and [`KernelStack`]
+//!
+//! Every process is tagged by a unique pid, and every kernel stack (one per
+//! thread) by a unique kernel-stack id; both are handed out by a
+//! [`RecycleAllocator`] and released automatically (pid via [`PidHandle`],
+//! kernel-stack slot via [`KernelStack`]'s `Drop`) when the owner goes away.
+
+use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
+use crate::mm::{MapPermission, VirtAddr, KERNEL_SPACE};
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Simple stack-backed id allocator that hands out increasing ids and
+/// recycles released ones before growing further. Used for the pid and
+/// kernel-stack-slot namespaces; each process also holds one instance for
+/// its own tid namespace ([`super::process`]).
+pub struct RecycleAllocator {
+    current: usize,
+    recycled: Vec<usize>,
+}
+
+impl RecycleAllocator {
+    /// Create an empty `RecycleAllocator`
+    pub fn new() -> Self {
+        RecycleAllocator {
+            current: 0,
+            recycled: Vec::new(),
+        }
+    }
+
+    /// Allocate an id
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
+        } else {
+            self.current += 1;
+            self.current - 1
+        }
+    }
+
+    /// Recycle an id
+    pub fn dealloc(&mut self, id: usize) {
+        assert!(id < self.current);
+        assert!(
+            !self.recycled.iter().any(|i| *i == id),
+            "id {} has been deallocated!",
+            id
+        );
+        self.recycled.push(id);
+    }
+}
+
+lazy_static! {
+    static ref PID_ALLOCATOR: UPSafeCell<RecycleAllocator> =
+        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+    static ref KSTACK_ALLOCATOR: UPSafeCell<RecycleAllocator> =
+        unsafe { UPSafeCell::new(RecycleAllocator::new()) };
+}
+
+/// A handle to an allocated pid, freed automatically on drop (RAII)
+pub struct PidHandle(pub usize);
+
+impl Drop for PidHandle {
+    fn drop(&mut self) {
+        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+    }
+}
+
+/// Allocate a new pid
+pub fn pid_alloc() -> PidHandle {
+    PidHandle(PID_ALLOCATOR.exclusive_access().alloc())
+}
+
+/// Compute the `(bottom, top)` virtual addresses of the kernel stack
+/// belonging to the given kernel-stack slot. Kernel stacks are laid out
+/// below the trampoline page, each separated by a guard page.
+pub fn kernel_stack_position(kstack_id: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - kstack_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+    let bottom = top - KERNEL_STACK_SIZE;
+    (bottom, top)
+}
+
+/// A kernel stack, mapped into kernel space by its slot id and unmapped
+/// automatically when dropped. Every thread (including a process's initial
+/// one) owns exactly one.
+pub struct KernelStack {
+    kstack_id: usize,
+}
+
+impl KernelStack {
+    /// Allocate a fresh kernel-stack slot and map it
+    pub fn new() -> Self {
+        let kstack_id = KSTACK_ALLOCATOR.exclusive_access().alloc();
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(kstack_id);
+        KERNEL_SPACE.exclusive_access().insert_framed_area(
+            kernel_stack_bottom.into(),
+            kernel_stack_top.into(),
+            MapPermission::R | MapPermission::W,
+        );
+        KernelStack { kstack_id }
+    }
+
+    /// Push a value onto the top of this kernel stack and return its
+    /// kernel virtual address
+    #[allow(unused)]
+    pub fn push_on_top<T>(&self, value: T) -> *mut T
+    where
+        T: Sized,
+    {
+        let kernel_stack_top = self.get_top();
+        let ptr_mut = (kernel_stack_top - core::mem::size_of::<T>()) as *mut T;
+        unsafe {
+            *ptr_mut = value;
+        }
+        ptr_mut
+    }
+
+    /// Get the kernel virtual address of the top of this kernel stack
+    pub fn get_top(&self) -> usize {
+        let (_, kernel_stack_top) = kernel_stack_position(self.kstack_id);
+        kernel_stack_top
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        let (kernel_stack_bottom, _) = kernel_stack_position(self.kstack_id);
+        let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
+        KERNEL_SPACE
+            .exclusive_access()
+            .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+        KSTACK_ALLOCATOR.exclusive_access().dealloc(self.kstack_id);
+    }
+}
@@ -0,0 +1,134 @@
+//! Implementation of [`ProcessControlBlock`]
+//!
+//! Holds everything a process's threads share: its address space, its place
+//! in the process tree, and its heap. Per-thread state (trap context, kernel
+//! stack, scheduling) lives on [`super::TaskControlBlock`] instead — see
+//! that module for how the two fit together.
+
+use super::pid::{pid_alloc, PidHandle, RecycleAllocator};
+use super::TaskControlBlock;
+use crate::mm::MemorySet;
+use crate::sync::UPSafeCell;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::RefMut;
+
+/// Process control block: the pid never changes once allocated; everything
+/// that mutates over the process's lifetime lives behind `inner`.
+pub struct ProcessControlBlock {
+    /// Process identifier, released automatically when the process is dropped
+    pub pid: PidHandle,
+    inner: UPSafeCell<ProcessControlBlockInner>,
+}
+
+/// Mutable inner state of a [`ProcessControlBlock`]
+pub struct ProcessControlBlockInner {
+    /// This process's address space, shared by every one of its threads
+    pub memory_set: MemorySet,
+    /// Parent process, if any (weak so the tree doesn't leak via cycles)
+    pub parent: Option<Weak<ProcessControlBlock>>,
+    /// Children spawned by this process, reaped by `sys_waitpid`
+    pub children: Vec<Arc<ProcessControlBlock>>,
+    /// Exit code, filled in when the process's main thread exits; `None`
+    /// while the process is still alive
+    pub exit_code: Option<i32>,
+    /// Lowest address of the heap, fixed at process creation
+    pub heap_bottom: usize,
+    /// Current program break, moved by `sys_sbrk`
+    pub program_brk: usize,
+    /// Every thread of this process, indexed by tid; a slot is `None` once
+    /// that thread has been reaped by `sys_waittid`
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    /// Per-process tid recycle list backing [`TaskUserRes`]
+    task_res_allocator: RecycleAllocator,
+}
+
+impl ProcessControlBlockInner {
+    /// Whether this process has exited (its main thread called `sys_exit`)
+    pub fn is_zombie(&self) -> bool {
+        self.exit_code.is_some()
+    }
+
+    /// Allocate a tid for a new thread of this process
+    pub fn alloc_tid(&mut self) -> usize {
+        self.task_res_allocator.alloc()
+    }
+
+    /// Recycle a tid, called from `TaskUserRes`'s `Drop`
+    pub fn dealloc_tid(&mut self, tid: usize) {
+        self.task_res_allocator.dealloc(tid)
+    }
+}
+
+impl ProcessControlBlock {
+    /// Access the mutable inner state of this process
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// Build a fresh process (with no threads yet) from a named
+    /// application's ELF data, returning it alongside the `(ustack_base,
+    /// entry_point)` its first thread should start from
+    pub fn new(elf_data: &[u8]) -> (Arc<Self>, usize, usize) {
+        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let process = Arc::new(Self {
+            pid: pid_alloc(),
+            inner: unsafe {
+                UPSafeCell::new(ProcessControlBlockInner {
+                    memory_set,
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: None,
+                    heap_bottom: ustack_base,
+                    program_brk: ustack_base,
+                    tasks: Vec::new(),
+                    task_res_allocator: RecycleAllocator::new(),
+                })
+            },
+        });
+        (process, ustack_base, entry_point)
+    }
+
+    /// Deep-copy this process's address space into a freshly allocated
+    /// child, used by `sys_fork`; the caller still has to create and enqueue
+    /// the child's first thread
+    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let memory_set = MemorySet::from_existing_user(&parent_inner.memory_set);
+        let child = Arc::new(Self {
+            pid: pid_alloc(),
+            inner: unsafe {
+                UPSafeCell::new(ProcessControlBlockInner {
+                    memory_set,
+                    parent: Some(Arc::downgrade(self)),
+                    children: Vec::new(),
+                    exit_code: None,
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                    tasks: Vec::new(),
+                    task_res_allocator: RecycleAllocator::new(),
+                })
+            },
+        });
+        parent_inner.children.push(Arc::clone(&child));
+        child
+    }
+
+    /// Replace this process's address space with a freshly loaded
+    /// application image, returning the `(ustack_base, entry_point)` the
+    /// surviving thread should restart from. The caller is responsible for
+    /// tearing down every thread but itself first.
+    pub fn exec(&self, elf_data: &[u8]) -> (usize, usize) {
+        let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set = memory_set;
+        inner.heap_bottom = ustack_base;
+        inner.program_brk = ustack_base;
+        (ustack_base, entry_point)
+    }
+
+    /// Get the pid of this process
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+}
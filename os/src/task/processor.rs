@@ -4,11 +4,14 @@
 //! the current running state of CPU is recorded,
 //! and the replacement and transfer of control flow of different applications are executed.
 
+use super::trace::{self, TraceEvent};
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::fetch_task;
+use super::TaskStatus;
 use super::{TaskContext, TaskControlBlock};
-use crate::{config::MAX_SYSCALL_NUM, sync::UPSafeCell};
-use crate::{timer::get_time_us, trap::TrapContext};
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_us;
+use crate::trap::TrapContext;
 use alloc::sync::Arc;
 use lazy_static::*;
 
@@ -44,42 +47,6 @@ impl Processor {
     pub fn current(&self) -> Option<Arc<TaskControlBlock>> {
         self.current.as_ref().map(Arc::clone)
     }
-
-    /// Map virtual memory address to physical memory
-    pub fn mmap_current_task(&self, start: usize, len: usize, prot: usize) -> isize {
-        match &self.current {
-            None => -1,
-            Some(task) => task.mmap(start, len, prot),
-        }
-    }
-
-    /// Unmap virtual memory address to physical memory
-    pub fn munmap_current_task(&self, start: usize, len: usize) -> isize {
-        match &self.current {
-            None => -1,
-            Some(task) => task.munmap(start, len),
-        }
-    }
-
-    /// Get current task status
-    pub fn current_status(&self) -> TaskStatus {
-        self.current().unwrap().task_status()
-    }
-
-    /// Get current syscall times
-    pub fn syscall_times(&self) -> [u32; MAX_SYSCALL_NUM] {
-        self.current().unwrap().syscall_times()
-    }
-
-    /// Add syscall times
-    pub fn add_syscall_times(&self, syscall_id: usize) {
-        self.current().unwrap().add_syscall_times(syscall_id)
-    }
-
-    /// Get current task start time
-    pub fn start_time(&self) -> usize {
-        self.current().unwrap().start_time()
-    }
 }
 
 lazy_static! {
@@ -92,10 +59,14 @@ pub fn run_tasks() {
     loop {
         let mut processor = PROCESSOR.exclusive_access();
         if let Some(task) = fetch_task() {
+            let pid = task.process.upgrade().unwrap().getpid();
+            let tid = task.tid();
+            trace::emit(TraceEvent::Fetched, Some(pid), Some(tid), None, None);
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
+            let status_before = task_inner.task_status;
             task_inner.task_status = TaskStatus::Running;
             if task_inner.start_time == 0 {
                 task_inner.start_time = get_time_us();
@@ -106,6 +77,13 @@ pub fn run_tasks() {
             processor.current = Some(task);
             // release processor manually
             drop(processor);
+            trace::emit(
+                TraceEvent::IdleToTask,
+                Some(pid),
+                Some(tid),
+                Some(status_before),
+                Some(TaskStatus::Running),
+            );
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
@@ -148,35 +126,3 @@ pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
 }
-
-/// Map virtual memory address to physical memory
-pub fn mmap_current_task(start: usize, len: usize, prot: usize) -> isize {
-    PROCESSOR
-        .exclusive_access()
-        .mmap_current_task(start, len, prot)
-}
-
-/// Unmap virtual memory address to physical memory
-pub fn munmap_current_task(start: usize, len: usize) -> isize {
-    PROCESSOR.exclusive_access().munmap_current_task(start, len)
-}
-
-/// Get current task status
-pub fn current_status() -> TaskStatus {
-    PROCESSOR.exclusive_access().current_status()
-}
-
-/// Get current task syscall times
-pub fn syscall_times() -> [u32; MAX_SYSCALL_NUM] {
-    PROCESSOR.exclusive_access().syscall_times()
-}
-
-/// Get current task start time
-pub fn current_start_time() -> usize {
-    PROCESSOR.exclusive_access().start_time()
-}
-
-/// Add syscall time to current task
-pub fn add_syscall_times(syscall_id: usize) {
-    PROCESSOR.exclusive_access().add_syscall_times(syscall_id)
-}
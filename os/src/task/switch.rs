@@ -0,0 +1,11 @@
+//! Raw `__switch` context switch, implemented in assembly
+
+use super::TaskContext;
+
+core::arch::global_asm!(include_str!("switch.S"));
+
+extern "C" {
+    /// Switch kernel control flow from `current_task_cx_ptr` to
+    /// `next_task_cx_ptr`, saving the former and restoring the latter
+    pub fn __switch(current_task_cx_ptr: *mut TaskContext, next_task_cx_ptr: *const TaskContext);
+}
@@ -0,0 +1,171 @@
+//! Types related to thread management
+//!
+//! A [`TaskControlBlock`] is one thread: its own trap context, kernel stack
+//! and scheduling state. Everything a process's threads share — address
+//! space, process tree, heap — lives on [`super::process::ProcessControlBlock`]
+//! instead.
+
+use super::id::TaskUserRes;
+use super::pid::KernelStack;
+use super::process::ProcessControlBlock;
+use super::TaskContext;
+use crate::config::MAX_SYSCALL_NUM;
+use crate::mm::PhysPageNum;
+use crate::sync::UPSafeCell;
+use crate::trap::TrapContext;
+use alloc::sync::{Arc, Weak};
+use core::cell::RefMut;
+
+/// Thread control block
+pub struct TaskControlBlock {
+    /// The process this thread belongs to
+    pub process: Weak<ProcessControlBlock>,
+    /// This thread's private kernel stack
+    pub kernel_stack: KernelStack,
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+/// Mutable inner state of a [`TaskControlBlock`]
+pub struct TaskControlBlockInner {
+    /// This thread's tid, user stack and trap-context page; taken (and thus
+    /// unmapped) once the thread has exited
+    pub res: Option<TaskUserRes>,
+    /// The physical page number of the frame holding this thread's trap
+    /// context
+    pub trap_cx_ppn: PhysPageNum,
+    /// Saved registers for switching away from and back into this thread
+    pub task_cx: TaskContext,
+    /// Current scheduling status
+    pub task_status: TaskStatus,
+    /// Exit code, filled in on `sys_exit`, read by `sys_waittid`
+    pub exit_code: Option<i32>,
+    /// Timestamp (us) this thread was first scheduled, 0 if never run
+    pub start_time: usize,
+    /// Call count and cumulative service time for each syscall issued by
+    /// this thread, indexed by syscall id
+    pub syscall_profile: [SyscallRecord; MAX_SYSCALL_NUM],
+    /// Stride scheduling accumulator; the scheduler always picks the ready
+    /// thread with the smallest stride
+    pub stride: usize,
+    /// Stride scheduling priority, `>= 2`; smaller passes (so more CPU time)
+    /// for larger priorities
+    pub priority: usize,
+}
+
+/// The stride step added to a thread's `stride` each time it's scheduled,
+/// chosen large enough that priorities down to 2 still divide evenly enough
+/// to keep scheduling fair. See [`stride_less`] for why strides must be
+/// compared with wrapping arithmetic.
+pub const BIG_STRIDE: usize = 65536;
+
+/// Default stride scheduling priority assigned to freshly created threads
+pub const DEFAULT_PRIORITY: usize = 16;
+
+/// Compare two strides that may have wrapped around `usize::MAX`: the
+/// maximum pass per step is bounded by `BIG_STRIDE`, so the signed
+/// difference `a - b` (computed via `wrapping_sub`) stays within range and
+/// its sign bit tells us which one is really smaller.
+pub fn stride_less(a: usize, b: usize) -> bool {
+    (a.wrapping_sub(b) as isize) < 0
+}
+
+/// Call count and cumulative time (us) spent servicing a single syscall id.
+/// Laid out `repr(C)` so it can be copied straight into a user buffer by
+/// `sys_syscall_profile`.
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct SyscallRecord {
+    /// Number of times this syscall has been invoked
+    pub count: u32,
+    /// Cumulative time spent inside this syscall, in microseconds
+    pub time_us: usize,
+}
+
+impl TaskControlBlockInner {
+    /// Get the mutable reference to trap context of this thread
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+
+    fn get_status(&self) -> TaskStatus {
+        self.task_status
+    }
+
+    /// Whether this thread has already exited
+    pub fn is_zombie(&self) -> bool {
+        self.get_status() == TaskStatus::Zombie
+    }
+
+    /// Set this thread's stride scheduling priority, returning `prio`.
+    /// Rejects `prio < 2` with -1 (a priority of 0 would make
+    /// `BIG_STRIDE / priority` divide by zero).
+    pub fn set_priority(&mut self, prio: isize) -> isize {
+        if prio < 2 {
+            return -1;
+        }
+        self.priority = prio as usize;
+        prio
+    }
+}
+
+impl TaskControlBlock {
+    /// Access the mutable inner state of this thread
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// Get the token representing this thread's (i.e. its process's) page
+    /// table
+    pub fn get_user_token(&self) -> usize {
+        self.process
+            .upgrade()
+            .unwrap()
+            .inner_exclusive_access()
+            .memory_set
+            .token()
+    }
+
+    /// Create a new thread of `process`. Pass `alloc_user_res = false` when
+    /// the thread's user stack and trap-context page are already mapped
+    /// (e.g. a `fork` child, whose address space was deep-copied from its
+    /// parent).
+    pub fn new(process: Arc<ProcessControlBlock>, ustack_base: usize, alloc_user_res: bool) -> Self {
+        let res = TaskUserRes::new(Arc::clone(&process), ustack_base, alloc_user_res);
+        let trap_cx_ppn = res.trap_cx_ppn();
+        let kernel_stack = KernelStack::new();
+        let kstack_top = kernel_stack.get_top();
+        Self {
+            process: Arc::downgrade(&process),
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    res: Some(res),
+                    trap_cx_ppn,
+                    task_cx: TaskContext::goto_trap_return(kstack_top),
+                    task_status: TaskStatus::Ready,
+                    exit_code: None,
+                    start_time: 0,
+                    syscall_profile: [SyscallRecord::default(); MAX_SYSCALL_NUM],
+                    stride: 0,
+                    priority: DEFAULT_PRIORITY,
+                })
+            },
+        }
+    }
+
+    /// This thread's tid
+    pub fn tid(&self) -> usize {
+        self.inner_exclusive_access().res.as_ref().unwrap().tid
+    }
+}
+
+/// The execution status of a thread
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TaskStatus {
+    /// Ready to run, waiting to be scheduled
+    Ready,
+    /// Currently running on the CPU
+    Running,
+    /// Exited
+    Zombie,
+}
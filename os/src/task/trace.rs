@@ -0,0 +1,67 @@
+//! Runtime-toggleable task-switch tracing
+//!
+//! Off by default and free when disabled: every `emit` call is a flag check
+//! plus a branch. Flip it on with `sys_trace_ctl(true)` to get a timestamped
+//! timeline of every control-flow transition the scheduler makes,
+//! useful for debugging scheduling fairness or starvation without reasoning
+//! about the scheduler from cold.
+
+use crate::sync::UPSafeCell;
+use crate::timer::get_time_us;
+use lazy_static::*;
+
+use super::TaskStatus;
+
+/// A single control-flow transition in the scheduler
+#[derive(Debug)]
+pub enum TraceEvent {
+    /// `fetch_task` handed a ready task to `run_tasks`
+    Fetched,
+    /// The idle control flow switched into a task
+    IdleToTask,
+    /// A task switched back into the idle control flow
+    TaskToIdle,
+    /// A task was preempted by the timer interrupt
+    TimerPreempt,
+    /// A task exited
+    Exit,
+}
+
+lazy_static! {
+    static ref TRACE_ENABLED: UPSafeCell<bool> = unsafe { UPSafeCell::new(false) };
+}
+
+/// Turn task-switch tracing on or off
+pub fn trace_ctl(on: bool) {
+    *TRACE_ENABLED.exclusive_access() = on;
+}
+
+/// Whether task-switch tracing is currently enabled
+pub fn trace_enabled() -> bool {
+    *TRACE_ENABLED.exclusive_access()
+}
+
+/// Record a trace event, if tracing is enabled. `pid`/`tid` name the task
+/// the event is about. A process can have multiple concurrently-scheduled threads,
+/// so `tid` is what actually identifies which control flow the event
+/// belongs to; `pid` is kept alongside it for readability.
+pub fn emit(
+    event: TraceEvent,
+    pid: Option<usize>,
+    tid: Option<usize>,
+    before: Option<TaskStatus>,
+    after: Option<TaskStatus>,
+) {
+    if !trace_enabled() {
+        return;
+    }
+    info!(
+        "[trace] t={} event={:?} pid={:?} tid={:?} {:?} -> {:?}",
+        get_time_us(),
+        event,
+        pid,
+        tid,
+        before,
+        after
+    );
+}
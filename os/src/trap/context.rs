@@ -0,0 +1,54 @@
+//! Trap context: the user registers a thread needs saved/restored across a
+//! trap into the kernel and back.
+
+use riscv::register::sstatus::{self, Sstatus, SPP};
+
+/// Trap context, laid out so `__alltraps`/`__restore` can save and restore it
+/// with plain offset loads/stores
+#[repr(C)]
+pub struct TrapContext {
+    /// General-purpose registers x0..x31
+    pub x: [usize; 32],
+    /// Supervisor status register
+    pub sstatus: Sstatus,
+    /// Supervisor exception program counter: the user instruction to resume
+    /// at (or, after a syscall, the one after it)
+    pub sepc: usize,
+    /// Kernel-space page table token, restored into `satp` on trap entry
+    pub kernel_satp: usize,
+    /// This thread's kernel stack pointer, restored into `sp` on trap entry
+    pub kernel_sp: usize,
+    /// The kernel's trap handler entry point, jumped to from `__alltraps`
+    pub trap_handler: usize,
+}
+
+impl TrapContext {
+    /// Write `sp` into `x[2]` (the `sp` register)
+    pub fn set_sp(&mut self, sp: usize) {
+        self.x[2] = sp;
+    }
+
+    /// Build the trap context a thread's first entry into user space resumes
+    /// from: user `sp` at `sp`, program counter at `entry`, and `sstatus.spp`
+    /// set to User so `sret` drops privilege.
+    pub fn app_init_context(
+        entry: usize,
+        sp: usize,
+        kernel_satp: usize,
+        kernel_sp: usize,
+        trap_handler: usize,
+    ) -> Self {
+        let mut sstatus = sstatus::read();
+        sstatus.set_spp(SPP::User);
+        let mut cx = Self {
+            x: [0; 32],
+            sstatus,
+            sepc: entry,
+            kernel_satp,
+            kernel_sp,
+            trap_handler,
+        };
+        cx.set_sp(sp);
+        cx
+    }
+}
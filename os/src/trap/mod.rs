@@ -0,0 +1,98 @@
+//! Trap handling
+//!
+//! Entry/exit from user space (saving and restoring a [`TrapContext`] across
+//! the trap) is handled by the `__alltraps`/`__restore` trampoline in
+//! `trap.S`, analogous to how [`crate::task::switch`] holds the raw
+//! `__switch` context switch. [`trap_handler`] is the Rust-side landing spot
+//! that trampoline jumps to: it reads `scause` to tell a syscall from a
+//! timer interrupt from a fault apart and routes accordingly.
+
+mod context;
+
+use crate::syscall::syscall;
+use crate::task::{
+    current_task, current_trap_cx, current_user_token, exit_current_and_run_next,
+    preempt_current_and_run_next,
+};
+use crate::timer::set_next_trigger;
+use riscv::register::{
+    scause::{self, Exception, Interrupt, Trap},
+    stval,
+};
+
+pub use context::TrapContext;
+
+core::arch::global_asm!(include_str!("trap.S"));
+
+extern "C" {
+    /// Restore the current thread's [`TrapContext`] at `trap_cx_ptr` and
+    /// `sret` back to user space under page table `user_satp`
+    fn __restore(trap_cx_ptr: usize, user_satp: usize) -> !;
+}
+
+/// Handle a trap from user space: a syscall, a timer interrupt, or a fault.
+/// Reached via the `__alltraps` trampoline with the thread's [`TrapContext`]
+/// already saved.
+#[no_mangle]
+pub fn trap_handler() -> ! {
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        Trap::Exception(Exception::UserEnvCall) => {
+            let mut cx = current_trap_cx();
+            cx.sepc += 4;
+            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            cx = current_trap_cx();
+            cx.x[10] = result as usize;
+        }
+        Trap::Exception(Exception::StoreFault)
+        | Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::InstructionFault)
+        | Trap::Exception(Exception::InstructionPageFault)
+        | Trap::Exception(Exception::LoadFault)
+        | Trap::Exception(Exception::LoadPageFault) => {
+            trace!(
+                "kernel: trap_handler: pagefault, scause={:?}, stval={:#x}, token={:#x}",
+                scause.cause(),
+                stval,
+                current_user_token(),
+            );
+            exit_current_and_run_next(-2);
+        }
+        Trap::Exception(Exception::IllegalInstruction) => {
+            trace!("kernel: trap_handler: IllegalInstruction");
+            exit_current_and_run_next(-3);
+        }
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            // Unlike a voluntary sys_yield (traced as TaskToIdle), this is a
+            // timer-triggered preemption, traced as TimerPreempt instead.
+            set_next_trigger();
+            preempt_current_and_run_next();
+        }
+        _ => {
+            panic!(
+                "Unsupported trap {:?}, stval = {:#x}!",
+                scause.cause(),
+                stval
+            );
+        }
+    }
+    trap_return();
+}
+
+/// Restore the current thread's [`TrapContext`] and return to user space
+pub fn trap_return() -> ! {
+    // `__restore` reads the trap context after switching to the user page
+    // table, so it needs the page's user-space address
+    let trap_cx_ptr = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .trap_cx_user_va();
+    let user_satp = current_user_token();
+    unsafe {
+        __restore(trap_cx_ptr, user_satp);
+    }
+}